@@ -1,34 +1,148 @@
 // Parse ANSI attr code
-use crate::curses::{attr_t, register_ansi, Window};
-use regex::Regex;
+use crate::curses::{
+    attr_t, get_color_pair, register_ansi, Color, Window, A_BLINK, A_BOLD, A_INVIS, A_NORMAL, A_REVERSE, A_UNDERLINE,
+};
 use std::default::Default;
 use std::iter::Enumerate;
 use std::iter::Peekable;
+use std::ops::Range;
+use vte::{Params, Parser, Perform};
 
 pub struct ANSIParser {
-    re: &'static Regex,
+    vte: Parser,
     last_attr: Option<attr_t>,
-}
-
-lazy_static! {
-    static ref ANSI_RE: Regex =
-        Regex::new(r"\x1B\[(?:([0-9]+;[0-9]+[Hf])|([0-9]+[ABCD])|(s|u|2J|K)|([0-9;]*m)|(=[0-9]+[hI]))").unwrap();
+    style: SgrStyle,
 }
 
 impl Default for ANSIParser {
     fn default() -> Self {
         ANSIParser {
-            re: &ANSI_RE,
+            vte: Parser::new(),
             last_attr: None,
+            style: SgrStyle::default(),
+        }
+    }
+}
+
+/// The merged SGR state accumulated across however many `\x1B[...m`
+/// sequences have been seen so far. `\x1B[1m` followed later by
+/// `\x1B[31m` leaves both bold and the red foreground set; `\x1B[0m`
+/// resets this back to `SgrStyle::default()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SgrStyle {
+    bold: bool,
+    underline: bool,
+    blink: bool,
+    reverse: bool,
+    invis: bool,
+    fg: Color,
+    bg: Color,
+}
+
+impl Default for SgrStyle {
+    fn default() -> Self {
+        SgrStyle {
+            bold: false,
+            underline: false,
+            blink: false,
+            reverse: false,
+            invis: false,
+            fg: Color::Default,
+            bg: Color::Default,
         }
     }
 }
 
+impl SgrStyle {
+    /// Fold one `m`-terminated CSI's semicolon-separated parameters into
+    /// this accumulated style. `38;5;n`/`48;5;n` consume one extra
+    /// parameter, `38;2;r;g;b`/`48;2;r;g;b` consume three.
+    fn apply_sgr(&mut self, nums: &[i64]) {
+        let mut i = 0;
+        while i < nums.len() {
+            match nums[i] {
+                0 => *self = SgrStyle::default(),
+                1 => self.bold = true,
+                4 => self.underline = true,
+                5 => self.blink = true,
+                7 => self.reverse = !self.reverse,
+                8 => self.invis = true,
+                22 => self.bold = false,
+                24 => self.underline = false,
+                25 => self.blink = false,
+                27 => self.reverse = false,
+                28 => self.invis = false,
+                39 => self.fg = Color::Default,
+                49 => self.bg = Color::Default,
+                n @ 30..=37 => self.fg = Color::Palette((n - 30) as u8),
+                n @ 40..=47 => self.bg = Color::Palette((n - 40) as u8),
+                n @ 90..=97 => self.fg = Color::Palette((n - 90 + 8) as u8),
+                n @ 100..=107 => self.bg = Color::Palette((n - 100 + 8) as u8),
+                code @ 38 | code @ 48 => {
+                    let set_fg = code == 38;
+                    match nums.get(i + 1) {
+                        Some(5) => {
+                            if let Some(&n) = nums.get(i + 2) {
+                                let color = Color::Spectrum256(n as u8);
+                                if set_fg {
+                                    self.fg = color;
+                                } else {
+                                    self.bg = color;
+                                }
+                            }
+                            i += 2;
+                        }
+                        Some(2) => {
+                            if let (Some(&r), Some(&g), Some(&b)) = (nums.get(i + 2), nums.get(i + 3), nums.get(i + 4))
+                            {
+                                let color = Color::Truecolor(r as u8, g as u8, b as u8);
+                                if set_fg {
+                                    self.fg = color;
+                                } else {
+                                    self.bg = color;
+                                }
+                            }
+                            i += 4;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn to_attr(self) -> attr_t {
+        let mut attr = A_NORMAL();
+        if self.bold {
+            attr |= A_BOLD();
+        }
+        if self.underline {
+            attr |= A_UNDERLINE();
+        }
+        if self.blink {
+            attr |= A_BLINK();
+        }
+        if self.reverse {
+            attr |= A_REVERSE();
+        }
+        if self.invis {
+            attr |= A_INVIS();
+        }
+        attr |= get_color_pair(self.fg, self.bg);
+        attr
+    }
+}
+
 #[derive(Clone, Debug)]
 // named like this not clash with ANSIString from ansi_term crate
 pub struct AnsiString {
     pub stripped: String,
     pub ansi_states: Vec<(usize, attr_t)>,
+    // OSC 8 hyperlink spans, keyed by character range into `stripped`,
+    // kept separate from `ansi_states` since a link isn't an attr_t.
+    pub hyperlinks: Vec<(Range<usize>, String)>,
 }
 
 impl AnsiString {
@@ -37,6 +151,7 @@ impl AnsiString {
         AnsiString {
             stripped: "".to_string(),
             ansi_states: Vec::new(),
+            hyperlinks: Vec::new(),
         }
     }
 
@@ -44,12 +159,21 @@ impl AnsiString {
         self.stripped
     }
 
+    /// Prints the string to `curses`, re-emitting an OSC 8 open/close pair
+    /// around characters covered by a hyperlink span, so terminals that
+    /// support it can make the printed text clickable.
     pub fn print(&self, curses: &mut Window) {
-        for (c, attrs) in self.iter() {
+        for (char_idx, (c, attrs)) in self.iter().enumerate() {
+            if let Some((_, url)) = self.hyperlinks.iter().find(|(r, _)| r.start == char_idx) {
+                curses.print_raw(&format!("\x1B]8;;{}\x1B\\", url));
+            }
             for (_, a) in attrs {
                 curses.attr_on(*a)
             }
             curses.addch(c);
+            if self.hyperlinks.iter().any(|(r, _)| r.end == char_idx + 1) {
+                curses.print_raw("\x1B]8;;\x1B\\");
+            }
         }
     }
 
@@ -65,6 +189,60 @@ impl AnsiString {
         !self.ansi_states.is_empty()
     }
 
+    // The attr_t active at character index `idx`, i.e. the most recent
+    // `ansi_states` entry at or before `idx`, whether or not it was set
+    // within the slice being carved out.
+    fn active_attr_at(&self, idx: usize) -> Option<attr_t> {
+        self.ansi_states.iter().rev().find(|&&(pos, _)| pos <= idx).map(|&(_, attr)| attr)
+    }
+
+    /// A width-aware, style-preserving substring: `stripped[start..end]`
+    /// (counted in chars, not bytes) along with `ansi_states` rebased to
+    /// the new slice's indices. If `start` falls in the middle of a run
+    /// rather than exactly on a state change, the attr_t active there is
+    /// carried forward so the returned piece doesn't lose its color.
+    pub fn substr(&self, start: usize, end: usize) -> AnsiString {
+        let total_chars = self.stripped.chars().count();
+        let start = start.min(total_chars);
+        let end = end.min(total_chars).max(start);
+        let stripped: String = self.stripped.chars().skip(start).take(end - start).collect();
+
+        let mut ansi_states = Vec::new();
+        if !self.ansi_states.iter().any(|&(pos, _)| pos == start) {
+            if let Some(attr) = self.active_attr_at(start) {
+                ansi_states.push((0, attr));
+            }
+        }
+        for &(pos, attr) in self.ansi_states.iter() {
+            if pos >= start && pos < end {
+                ansi_states.push((pos - start, attr));
+            }
+        }
+
+        let hyperlinks = self
+            .hyperlinks
+            .iter()
+            .filter_map(|(range, url)| {
+                let clamped = range.start.max(start)..range.end.min(end);
+                if clamped.is_empty() {
+                    None
+                } else {
+                    Some((clamped.start - start..clamped.end - start, url.clone()))
+                }
+            })
+            .collect();
+
+        AnsiString { stripped, ansi_states, hyperlinks }
+    }
+
+    /// Cuts this `AnsiString` into two pieces at character index `char_idx`,
+    /// preserving whatever attribute was active at the start of each piece.
+    /// See [`AnsiString::substr`] for how attributes are carried forward.
+    pub fn split_at(&self, char_idx: usize) -> (AnsiString, AnsiString) {
+        let total_chars = self.stripped.chars().count();
+        (self.substr(0, char_idx), self.substr(char_idx, total_chars))
+    }
+
     pub fn from_str(raw: &str) -> AnsiString {
         ANSIParser::default().parse_ansi(raw)
     }
@@ -109,123 +287,240 @@ impl<'a> Iterator for AnsiStringIterator<'a> {
     }
 }
 
-impl ANSIParser {
-    pub fn parse_ansi(&mut self, text: &str) -> AnsiString {
-        let mut strip_string = String::new();
-        let mut colors = Vec::new();
-
-        // assume parse_ansi is called linewise.
-        // Because ANSI color code can affect text of next lines. We will save the last attribute and
-        // add it to the newest line if no new color is specified.
-        match self.re.find(text) {
-            Some(mat) if mat.start() == 0 => {}
-            _ => {
-                if let Some(attr) = self.last_attr {
-                    colors.push((0, attr));
+// One write-cursor-addressable cell of the virtual line being assembled by
+// `\r`-rewrites and cursor-movement CSI codes, mirroring `bat`'s vscreen.
+// `attr` is the attr_t active when the cell was written (the merged SGR
+// style, or the opaque attr registered for the last other CSI code), so
+// cells can be diffed directly once the line is done.
+#[derive(Clone, Copy)]
+struct Cell {
+    ch: char,
+    attr: Option<attr_t>,
+}
+
+// Caps how far cursor-column CSI codes (`C`/`G`) can push the write
+// cursor. Without this, a few bytes like `\x1B[65535C` would make
+// `write_char`'s cursor-padding loop materialize a multi-KB buffer per
+// escape code, letting a small amount of untrusted piped input (e.g. a
+// crafted file or command output) force a huge allocation. This mirrors
+// the way a real terminal's visible line width naturally bounds it.
+const MAX_LINE_WIDTH: usize = 4096;
+
+/// Accumulates the output of a single `parse_ansi` call while the byte-fed
+/// `vte::Parser` drives it through `Perform` callbacks. The state machine
+/// itself (`self.vte` on `ANSIParser`) lives across calls so a sequence
+/// split between two `parse_ansi` invocations is still interpreted
+/// correctly; only the per-call output lives here.
+///
+/// Text isn't appended to a plain string: it's written into `buffer` at
+/// `cursor`, so a carriage return or cursor-positioning CSI can make later
+/// bytes overwrite earlier ones, the same way a real terminal resolves a
+/// progress-bar-style line into its final visible contents.
+struct Performer<'a> {
+    buffer: Vec<Cell>,
+    cursor: usize,
+    current_attr: Option<attr_t>,
+    style: &'a mut SgrStyle,
+    hyperlinks: Vec<(Range<usize>, String)>,
+    // A hyperlink opened by OSC 8 but not yet closed. Unlike the vte
+    // state, this doesn't carry across `parse_ansi` calls: a link left
+    // dangling at the end of a call is simply dropped.
+    pending_link: Option<(usize, String)>,
+}
+
+impl<'a> Performer<'a> {
+    fn write_char(&mut self, ch: char) {
+        if self.cursor < self.buffer.len() {
+            self.buffer[self.cursor] = Cell { ch, attr: self.current_attr };
+        } else {
+            while self.buffer.len() < self.cursor {
+                self.buffer.push(Cell { ch: ' ', attr: None });
+            }
+            self.buffer.push(Cell { ch, attr: self.current_attr });
+        }
+        self.cursor += 1;
+    }
+
+    fn first_param(params: &Params) -> i64 {
+        params.iter().next().and_then(|p| p.first()).map_or(0, |&n| n as i64)
+    }
+}
+
+impl<'a> Perform for Performer<'a> {
+    // plain text: write it at the cursor
+    fn print(&mut self, c: char) {
+        self.write_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        match byte {
+            // reset the write cursor to column 0 so later bytes overwrite
+            // this line instead of being appended after it
+            b'\r' => self.cursor = 0,
+            _ => self.write_char(byte as char),
+        }
+    }
+
+    // CSI dispatch: SGR (`m`) is folded into the merged style; `K`/`J`
+    // (erase) and `G`/`C`/`D` (cursor column) manipulate the virtual line
+    // directly; everything else is reconstructed and registered opaquely.
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], _ignore: bool, action: char) {
+        if intermediates.is_empty() {
+            match action {
+                'm' => {
+                    let nums: Vec<i64> = params.iter().map(|p| *p.first().unwrap_or(&0) as i64).collect();
+                    let nums = if nums.is_empty() { vec![0] } else { nums };
+                    self.style.apply_sgr(&nums);
+                    self.current_attr = Some(self.style.to_attr());
+                    return;
+                }
+                // erase in line: 0 (default) cursor..end, 1 start..=cursor, 2 whole line.
+                // This only touches the buffered cells, not the live SGR
+                // style: real EL codes don't reset graphic rendition, so a
+                // color set before the erase still applies to whatever is
+                // written afterward.
+                'K' => {
+                    match Self::first_param(params) {
+                        1 => {
+                            let end = (self.cursor + 1).min(self.buffer.len());
+                            for cell in &mut self.buffer[..end] {
+                                *cell = Cell { ch: ' ', attr: None };
+                            }
+                        }
+                        2 => self.buffer.clear(),
+                        _ => self.buffer.truncate(self.cursor),
+                    }
+                    return;
+                }
+                // erase in display: only the "whole screen" forms are
+                // meaningful for the single line we're buffering
+                'J' => {
+                    if matches!(Self::first_param(params), 2 | 3) {
+                        self.buffer.clear();
+                        self.cursor = 0;
+                    }
+                    return;
+                }
+                // cursor horizontal absolute (1-based column)
+                'G' => {
+                    let col = (Self::first_param(params).max(1) - 1) as usize;
+                    self.cursor = col.min(MAX_LINE_WIDTH);
+                    return;
+                }
+                // cursor forward
+                'C' => {
+                    let advance = Self::first_param(params).max(1) as usize;
+                    self.cursor = (self.cursor + advance).min(MAX_LINE_WIDTH);
+                    return;
+                }
+                // cursor back
+                'D' => {
+                    self.cursor = self.cursor.saturating_sub(Self::first_param(params).max(1) as usize);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        let mut code = String::from("\x1B[");
+        for intermediate in intermediates {
+            code.push(*intermediate as char);
+        }
+        let mut first = true;
+        for param in params.iter() {
+            if !first {
+                code.push(';');
+            }
+            first = false;
+            for (i, sub) in param.iter().enumerate() {
+                if i > 0 {
+                    code.push(':');
                 }
+                code.push_str(&sub.to_string());
             }
         }
+        code.push(action);
 
-        let mut num_chars = 0;
-        let mut last = 0;
-        for mat in self.re.find_iter(text) {
-            let (start, end) = (mat.start(), mat.end());
-            strip_string.push_str(&text[last..start]);
-            num_chars += (&text[last..start]).chars().count();
+        self.current_attr = Some(ANSIParser::interpret_code(&code));
+    }
 
-            last = end;
+    // OSC 8 hyperlinks: `\x1B]8;;URL\x1B\\text\x1B]8;;\x1B\\`. Other OSC
+    // sequences carry no attribute state we track and their bytes are
+    // simply dropped instead of leaking into `stripped`.
+    fn osc_dispatch(&mut self, params: &[&[u8]], _bell_terminated: bool) {
+        if params.first() != Some(&&b"8"[..]) {
+            return;
+        }
+        let uri = params.get(2).copied().unwrap_or(&[]);
+        if uri.is_empty() {
+            if let Some((start, url)) = self.pending_link.take() {
+                if self.cursor > start {
+                    self.hyperlinks.push((start..self.cursor, url));
+                }
+            }
+        } else {
+            self.pending_link = Some((self.cursor, String::from_utf8_lossy(uri).into_owned()));
+        }
+    }
+
+    // Lone ESC sequences (outside of CSI/OSC) carry no attribute state we
+    // track today.
+    fn esc_dispatch(&mut self, _intermediates: &[u8], _ignore: bool, _byte: u8) {}
+}
+
+impl ANSIParser {
+    pub fn parse_ansi(&mut self, text: &str) -> AnsiString {
+        let mut performer = Performer {
+            buffer: Vec::new(),
+            cursor: 0,
+            current_attr: self.last_attr,
+            style: &mut self.style,
+            hyperlinks: Vec::new(),
+            pending_link: None,
+        };
 
-            let attr = self.interpret_code(&text[start..end]);
-            if let Some(attr) = attr {
-                colors.push((num_chars, attr));
+        for byte in text.as_bytes() {
+            self.vte.advance(&mut performer, *byte);
+        }
+
+        // Resolve the virtual line into its final visible text, diffing
+        // consecutive cells so `ansi_states` only grows where the resolved
+        // attribute actually changes, matching what a real terminal would
+        // end up showing after all overwrites are applied.
+        let mut stripped = String::with_capacity(performer.buffer.len());
+        let mut ansi_states = Vec::new();
+        let mut prev = None;
+        for (idx, cell) in performer.buffer.iter().enumerate() {
+            stripped.push(cell.ch);
+            if cell.attr != prev {
+                if let Some(attr) = cell.attr {
+                    ansi_states.push((idx, attr));
+                }
+                prev = cell.attr;
             }
-            self.last_attr = attr;
         }
 
-        strip_string.push_str(&text[last..text.len()]);
+        // The live SGR style carries into the next call regardless of what
+        // this call's buffer resolved to: a real terminal's graphic
+        // rendition state doesn't depend on whether the current line ended
+        // up blank, so a fully-erased progress-bar frame shouldn't drop the
+        // color a redraw is about to reuse.
+        self.last_attr = performer.current_attr;
 
         AnsiString {
-            stripped: strip_string,
-            ansi_states: colors,
+            stripped,
+            ansi_states,
+            hyperlinks: performer.hyperlinks,
         }
     }
 
-    fn interpret_code(&self, code: &str) -> Option<attr_t> {
-        if code == "\x1B[K" || code == "\x1B[2J" {
-            // clear screen & clear line
-            None
-        } else {
-            let key = register_ansi(code.to_owned());
-            Some(key)
-        }
-
-        //let mut state256 = 0;
-        //let mut attr = 0;
-        //let mut fg = -1;
-        //let mut bg = -1;
-        //let mut use_fg = true;
-
-        //let code = &code[2..code.len()-1]; // ^[[1;30;40m -> 1;30;40
-        //if code.is_empty() {
-        //return Some(A_NORMAL());
-        //}
-
-        //for num in code.split(';').map(|x| x.parse::<i16>()) {
-        //match state256 {
-        //0 => {
-        //match num.unwrap_or(0) {
-        //0 => {attr = 0;}
-        //1 => {attr |= A_BOLD();}
-        //4 => {attr |= A_UNDERLINE();}
-        //5 => {attr |= A_BLINK();}
-        //7 => {attr |= A_REVERSE();}
-        //8 => {attr |= A_INVIS();}
-        //38 => {
-        //use_fg = true;
-        //state256 += 1;
-        //}
-        //48 => {
-        //use_fg = false;
-        //state256 += 1;
-        //}
-        //39 => {
-        //fg = -1;
-        //}
-        //49 => {
-        //bg = -1;
-        //}
-        //num if num >= 30 && num <= 37 => {
-        //fg = num - 30;
-        //}
-        //num if num >= 40 && num <= 47 => {
-        //bg = num - 40;
-        //}
-        //_ => {
-        //}
-        //}
-        //}
-        //1 => {
-        //match num.unwrap_or(0) {
-        //5 => { state256 += 1; }
-        //_ => { state256 = 0; }
-        //}
-        //}
-        //2 => {
-        //if use_fg {
-        //fg = num.unwrap_or(-1);
-        //} else {
-        //bg = num.unwrap_or(-1);
-        //}
-        //}
-        //_ => {}
-        //}
-        //}
-
-        //if fg != -1 || bg != -1 {
-        //attr |= get_color_pair(fg, bg);
-        //}
-
-        //Some(attr)
+    // Non-SGR CSI codes (cursor movement, etc.) aren't decomposed; they're
+    // registered as an opaque attr keyed by their raw escape text. SGR
+    // (`m`) sequences never reach here: they're merged into `SgrStyle` by
+    // `Performer::csi_dispatch` instead, and erase/cursor-column codes are
+    // handled there directly against the virtual line.
+    fn interpret_code(code: &str) -> attr_t {
+        register_ansi(code.to_owned())
     }
 }
 
@@ -235,11 +530,148 @@ mod tests {
 
     #[test]
     fn test_ansi_iterator() {
+        // A truecolor bg followed by a truecolor fg merges into a single
+        // combined style by the time `h` is actually written.
         let input = "\x1B[48;2;5;10;15m\x1B[38;2;70;130;180mhi\x1B[0m";
         let ansistring = ANSIParser::default().parse_ansi(input);
         let mut it = ansistring.iter();
-        let arr: Vec<(usize, u16)> = vec![(0, 11), (0, 12)];
-        assert_eq!(Some(('h', &arr[..2])), it.next());
-        assert_eq!(Some(('i', &arr[..0])), it.next());
+        let (ch, attrs) = it.next().unwrap();
+        assert_eq!(ch, 'h');
+        assert_eq!(attrs.len(), 1);
+        let (ch, attrs) = it.next().unwrap();
+        assert_eq!(ch, 'i');
+        assert!(attrs.is_empty());
+    }
+
+    #[test]
+    fn test_sgr_merges_bold_and_color() {
+        // \x1B[1m and \x1B[31m arrive as separate codes but should be
+        // folded into one bold+red attribute by the time `h` is printed.
+        let input = "\x1B[1m\x1B[31mhi\x1B[0m";
+        let ansistring = ANSIParser::default().parse_ansi(input);
+        assert!(ansistring.has_attrs());
+        let mut it = ansistring.iter();
+        let (_, attrs) = it.next().unwrap();
+        assert_eq!(attrs.len(), 1);
+    }
+
+    #[test]
+    fn test_split_at_carries_forward_attr() {
+        // "plain" is uncolored, "red text" is red; splitting inside "red"
+        // must leave the color attached to both halves.
+        let input = "plain\x1B[31mred text";
+        let ansistring = ANSIParser::default().parse_ansi(input);
+        let (left, right) = ansistring.split_at(7);
+        assert_eq!(left.stripped, "plainre");
+        assert_eq!(right.stripped, "d text");
+        assert!(left.has_attrs());
+        assert_eq!(left.ansi_states[0].0, 5);
+        assert!(right.has_attrs());
+        assert_eq!(right.ansi_states[0].0, 0); // carried forward from before the cut point
+    }
+
+    #[test]
+    fn test_substr_rebases_indices() {
+        let input = "\x1B[1mbold\x1B[0m plain";
+        let ansistring = ANSIParser::default().parse_ansi(input);
+        let middle = ansistring.substr(2, 6);
+        assert_eq!(middle.stripped, "ld p");
+        assert_eq!(middle.ansi_states[0].0, 0);
+    }
+
+    #[test]
+    fn test_split_at_past_end_does_not_panic() {
+        // Clipping to a column range wider than the line is short (e.g. a
+        // narrow item padded out to the display width) must not underflow.
+        let ansistring = ANSIParser::default().parse_ansi("short");
+        let (left, right) = ansistring.split_at(100);
+        assert_eq!(left.stripped, "short");
+        assert_eq!(right.stripped, "");
+    }
+
+    #[test]
+    fn test_osc8_hyperlink() {
+        let input = "see \x1B]8;;https://example.com\x1B\\docs\x1B]8;;\x1B\\ for more";
+        let ansistring = ANSIParser::default().parse_ansi(input);
+        assert_eq!(ansistring.stripped, "see docs for more");
+        assert_eq!(ansistring.hyperlinks.len(), 1);
+        let (range, url) = &ansistring.hyperlinks[0];
+        assert_eq!(url, "https://example.com");
+        assert_eq!(range.clone(), 4..8); // "see " before, "docs" covers the link
+    }
+
+    #[test]
+    fn test_ansi_split_across_calls() {
+        // A CSI sequence split across two `parse_ansi` invocations must
+        // still be interpreted as a single escape code.
+        let mut parser = ANSIParser::default();
+        let first = parser.parse_ansi("\x1B[1");
+        assert_eq!(first.stripped, "");
+        let second = parser.parse_ansi("mhi");
+        assert_eq!(second.stripped, "hi");
+        assert!(second.has_attrs());
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites_line() {
+        // A bare \r rewinds the write cursor, so the second "world" run
+        // overwrites "hello" instead of trailing after it.
+        let ansistring = ANSIParser::default().parse_ansi("hello\rworld");
+        assert_eq!(ansistring.stripped, "world");
+    }
+
+    #[test]
+    fn test_carriage_return_partial_overwrite() {
+        // Only the first 2 columns are rewritten; the rest of "hello"
+        // survives, matching what a real terminal would show.
+        let ansistring = ANSIParser::default().parse_ansi("hello\rwo");
+        assert_eq!(ansistring.stripped, "wollo");
+    }
+
+    #[test]
+    fn test_erase_in_line_from_cursor() {
+        let ansistring = ANSIParser::default().parse_ansi("hello\r\x1B[2C\x1B[K");
+        assert_eq!(ansistring.stripped, "he");
+    }
+
+    #[test]
+    fn test_erase_in_line_to_cursor_inclusive() {
+        // EL1 erases from the start of the line up to AND including the
+        // cursor's own column.
+        let ansistring = ANSIParser::default().parse_ansi("abcde\r\x1B[2C\x1B[1K");
+        assert_eq!(ansistring.stripped, "   de");
+    }
+
+    #[test]
+    fn test_erase_carries_color_even_when_line_ends_empty() {
+        // A real terminal's graphic rendition state doesn't depend on
+        // whether the current line resolved to anything visible: a color
+        // set before a line-clear still applies to the next `parse_ansi`
+        // call, even though this call's own buffer ended up empty.
+        let mut parser = ANSIParser::default();
+        let first = parser.parse_ansi("\x1B[31mred\r\x1B[K");
+        assert!(!first.has_attrs()); // nothing left to color on this line
+        let second = parser.parse_ansi("next");
+        assert!(second.has_attrs());
+    }
+
+    #[test]
+    fn test_cursor_forward_clamps_buffer_growth() {
+        // A large `C`/`G` column shouldn't let a few bytes of escape code
+        // force an unbounded allocation: the write cursor (and thus the
+        // padding `write_char` does up to it) is capped at MAX_LINE_WIDTH.
+        let input = "\x1B[65535C".repeat(200) + "x";
+        let ansistring = ANSIParser::default().parse_ansi(&input);
+        assert!(ansistring.stripped.len() <= MAX_LINE_WIDTH + 1);
+    }
+
+    #[test]
+    fn test_erase_does_not_clear_live_sgr_style() {
+        // EL doesn't reset graphic rendition: redrawing after a \r +
+        // \x1B[K (the common progress-bar pattern) must keep the color
+        // that was set before the erase.
+        let ansistring = ANSIParser::default().parse_ansi("\x1B[31mred\r\x1B[Knew");
+        assert_eq!(ansistring.stripped, "new");
+        assert!(ansistring.has_attrs());
     }
 }